@@ -14,7 +14,7 @@ use self::access::gen_state_access_trace;
 use crate::error::Error;
 use crate::evm::opcodes::{gen_associated_ops, gen_begin_tx_ops, gen_end_tx_ops};
 use crate::operation::{CallContextField, RW};
-use crate::rpc::GethClient;
+use crate::rpc::{GethClient, ProofFetchConfig};
 use crate::state_db::{self, CodeDB, StateDB};
 pub use access::{Access, AccessSet, AccessValue, CodeSource};
 pub use block::{Block, BlockContext};
@@ -219,6 +219,7 @@ pub struct BuilderClient<P: JsonRpcClient> {
     cli: GethClient<P>,
     chain_id: Word,
     history_hashes: Vec<Word>,
+    proof_fetch_config: ProofFetchConfig,
 }
 
 impl<P: JsonRpcClient> BuilderClient<P> {
@@ -231,9 +232,17 @@ impl<P: JsonRpcClient> BuilderClient<P> {
             chain_id: chain_id.into(),
             // TODO: Get history hashes
             history_hashes: Vec::new(),
+            proof_fetch_config: ProofFetchConfig::default(),
         })
     }
 
+    /// Set the parallelism and retry options used to fetch the state proofs.
+    /// The minimum interval between requests is set on the [`GethClient`].
+    pub fn with_proof_fetch_config(mut self, config: ProofFetchConfig) -> Self {
+        self.proof_fetch_config = config;
+        self
+    }
+
     /// Step 1. Query geth for Block, Txs and TxExecTraces
     pub async fn get_block(
         &self,
@@ -279,24 +288,22 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         ),
         Error,
     > {
-        let mut proofs = Vec::new();
-        for (address, key_set) in access_set.state {
-            let mut keys: Vec<Word> = key_set.iter().cloned().collect();
-            keys.sort();
-            let proof = self
-                .cli
-                .get_proof(address, keys, (block_num - 1).into())
-                .await
-                .unwrap();
-            proofs.push(proof);
-        }
+        let requests = access_set
+            .state
+            .into_iter()
+            .map(|(address, key_set)| {
+                let mut keys: Vec<Word> = key_set.iter().cloned().collect();
+                keys.sort();
+                (address, keys)
+            })
+            .collect();
+        let proofs = self
+            .cli
+            .get_proofs(requests, (block_num - 1).into(), &self.proof_fetch_config)
+            .await?;
         let mut codes: HashMap<Address, Vec<u8>> = HashMap::new();
         for address in access_set.code {
-            let code = self
-                .cli
-                .get_code(address, (block_num - 1).into())
-                .await
-                .unwrap();
+            let code = self.cli.get_code(address, (block_num - 1).into()).await?;
             codes.insert(address, code);
         }
         Ok((proofs, codes))
@@ -7,8 +7,11 @@ use eth_types::{
     Transaction, Word, U64,
 };
 pub use ethers_core::types::BlockNumber;
-use ethers_providers::JsonRpcClient;
-use serde::Serialize;
+use ethers_providers::{HttpClientError, JsonRpcClient, ProviderError};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
 
 /// Serialize a type.
 ///
@@ -47,31 +50,134 @@ impl Default for GethLoggerConfig {
     }
 }
 
+/// Options for fetching many `eth_getProof` responses from a single provider.
+#[derive(Clone, Debug)]
+pub struct ProofFetchConfig {
+    /// Maximum number of requests in flight at the same time. Defaults to 1,
+    /// as public providers may ban clients sending bursts of requests.
+    pub parallelism: usize,
+    /// Number of times a request failing with a transport error or a rate
+    /// limit response is retried before giving up.
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled on every following retry.
+    pub initial_backoff: Duration,
+}
+
+impl Default for ProofFetchConfig {
+    fn default() -> Self {
+        Self {
+            parallelism: 1,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Spaces out requests so that at most one is sent per `interval`.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request slot is available and reserves it.
+    async fn wait(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let mut next = self.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.interval;
+    }
+}
+
+/// JSON-RPC error codes used by providers to reject requests over their rate
+/// limit: `-32005` ("limit exceeded", EIP-1474) and `429`.
+const RATE_LIMIT_ERROR_CODES: [i64; 2] = [-32005, 429];
+
+/// Returns whether the request failed because of the transport or because the
+/// provider rate limited it, in which case sending it again can succeed.
+///
+/// Only errors of the [`ethers_providers::Http`] transport are recognised;
+/// failures of any other transport are never retried. The http transport
+/// doesn't expose the status code, so an HTTP 429 response is recognised by
+/// its standard "Too Many Requests" reason phrase in the non-JSON body.
+fn is_transient(err: &Error) -> bool {
+    let err = match err {
+        Error::JSONRpcError(ProviderError::JsonRpcClientError(err)) => err,
+        _ => return false,
+    };
+    match err.downcast_ref::<HttpClientError>() {
+        Some(HttpClientError::ReqwestError(_)) => true,
+        Some(HttpClientError::JsonRpcError(err)) => RATE_LIMIT_ERROR_CODES.contains(&err.code),
+        // An HTTP 429 response doesn't carry a JSON-RPC body.
+        Some(HttpClientError::SerdeJson { text, .. }) => {
+            text.to_lowercase().contains("too many requests")
+        }
+        _ => false,
+    }
+}
+
 /// Placeholder structure designed to contain the methods that the BusMapping
 /// needs in order to enable Geth queries.
-pub struct GethClient<P: JsonRpcClient>(pub P);
+///
+/// Every request goes through a rate limiter shared by all the queries of the
+/// client. The provider is therefore no longer a public tuple field: build the
+/// client with [`GethClient::new`] or [`GethClient::with_min_request_interval`]
+/// and access the provider with [`GethClient::provider`].
+pub struct GethClient<P: JsonRpcClient> {
+    provider: P,
+    limiter: RateLimiter,
+}
 
 impl<P: JsonRpcClient> GethClient<P> {
     /// Generates a new `GethClient` instance.
     pub fn new(provider: P) -> Self {
-        Self(provider)
+        Self::with_min_request_interval(provider, Duration::ZERO)
     }
 
-    /// Calls `eth_coinbase` via JSON-RPC returning the coinbase of the network.
-    pub async fn get_coinbase(&self) -> Result<Address, Error> {
-        self.0
-            .request("eth_coinbase", ())
+    /// Generates a new `GethClient` instance which waits at least
+    /// `min_request_interval` between two requests sent to the provider.
+    pub fn with_min_request_interval(provider: P, min_request_interval: Duration) -> Self {
+        Self {
+            provider,
+            limiter: RateLimiter::new(min_request_interval),
+        }
+    }
+
+    /// Returns the underlying provider. Requests sent through it directly
+    /// bypass the rate limiter.
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.limiter.wait().await;
+        self.provider
+            .request(method, params)
             .await
             .map_err(|e| Error::JSONRpcError(e.into()))
     }
 
+    /// Calls `eth_coinbase` via JSON-RPC returning the coinbase of the network.
+    pub async fn get_coinbase(&self) -> Result<Address, Error> {
+        self.request("eth_coinbase", ()).await
+    }
+
     /// Calls `eth_chainId` via JSON-RPC returning the chain id of the network.
     pub async fn get_chain_id(&self) -> Result<u64, Error> {
-        let net_id: U64 = self
-            .0
-            .request("eth_chainId", ())
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))?;
+        let net_id: U64 = self.request("eth_chainId", ()).await?;
         Ok(net_id.as_u64())
     }
 
@@ -80,10 +186,7 @@ impl<P: JsonRpcClient> GethClient<P> {
     pub async fn get_block_by_hash(&self, hash: Hash) -> Result<Block<Transaction>, Error> {
         let hash = serialize(&hash);
         let flag = serialize(&true);
-        self.0
-            .request("eth_getBlockByHash", [hash, flag])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))
+        self.request("eth_getBlockByHash", [hash, flag]).await
     }
 
     /// Calls `eth_getBlockByNumber` via JSON-RPC returning a [`Block`]
@@ -95,10 +198,7 @@ impl<P: JsonRpcClient> GethClient<P> {
     ) -> Result<Block<Transaction>, Error> {
         let num = serialize(&block_num);
         let flag = serialize(&true);
-        self.0
-            .request("eth_getBlockByNumber", [num, flag])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))
+        self.request("eth_getBlockByNumber", [num, flag]).await
     }
 
     /// Calls `debug_traceBlockByHash` via JSON-RPC returning a
@@ -107,11 +207,8 @@ impl<P: JsonRpcClient> GethClient<P> {
     pub async fn trace_block_by_hash(&self, hash: Hash) -> Result<Vec<GethExecTrace>, Error> {
         let hash = serialize(&hash);
         let cfg = serialize(&GethLoggerConfig::default());
-        let resp: ResultGethExecTraces = self
-            .0
-            .request("debug_traceBlockByHash", [hash, cfg])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))?;
+        let resp: ResultGethExecTraces =
+            self.request("debug_traceBlockByHash", [hash, cfg]).await?;
         Ok(resp.0.into_iter().map(|step| step.result).collect())
     }
 
@@ -124,11 +221,8 @@ impl<P: JsonRpcClient> GethClient<P> {
     ) -> Result<Vec<GethExecTrace>, Error> {
         let num = serialize(&block_num);
         let cfg = serialize(&GethLoggerConfig::default());
-        let resp: ResultGethExecTraces = self
-            .0
-            .request("debug_traceBlockByNumber", [num, cfg])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))?;
+        let resp: ResultGethExecTraces =
+            self.request("debug_traceBlockByNumber", [num, cfg]).await?;
         Ok(resp.0.into_iter().map(|step| step.result).collect())
     }
 
@@ -140,11 +234,7 @@ impl<P: JsonRpcClient> GethClient<P> {
     ) -> Result<Vec<u8>, Error> {
         let address = serialize(&contract_address);
         let num = serialize(&block_num);
-        let resp: Bytes = self
-            .0
-            .request("eth_getCode", [address, num])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))?;
+        let resp: Bytes = self.request("eth_getCode", [address, num]).await?;
         Ok(resp.to_vec())
     }
 
@@ -160,29 +250,214 @@ impl<P: JsonRpcClient> GethClient<P> {
         let account = serialize(&account);
         let keys = serialize(&keys);
         let num = serialize(&block_num);
-        self.0
-            .request("eth_getProof", [account, keys, num])
+        self.request("eth_getProof", [account, keys, num]).await
+    }
+
+    /// Calls `eth_getProof` for every `(account, keys)` pair with at most
+    /// `config.parallelism` requests in flight, retrying requests that failed
+    /// with a transport error or a rate limit response with exponential
+    /// backoff. The proofs are returned in the order of `requests`.
+    pub async fn get_proofs(
+        &self,
+        requests: Vec<(Address, Vec<Word>)>,
+        block_num: BlockNumber,
+        config: &ProofFetchConfig,
+    ) -> Result<Vec<EIP1186ProofResponse>, Error> {
+        stream::iter(requests)
+            .map(|(account, keys)| self.get_proof_with_retry(account, keys, block_num, config))
+            .buffered(config.parallelism.max(1))
+            .try_collect()
             .await
-            .map_err(|e| Error::JSONRpcError(e.into()))
+    }
+
+    async fn get_proof_with_retry(
+        &self,
+        account: Address,
+        keys: Vec<Word>,
+        block_num: BlockNumber,
+        config: &ProofFetchConfig,
+    ) -> Result<EIP1186ProofResponse, Error> {
+        let mut backoff = config.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match self.get_proof(account, keys.clone(), block_num).await {
+                Err(err) if retries < config.max_retries && is_transient(&err) => {
+                    log::warn!(
+                        "eth_getProof for {:?} failed, retrying in {:?}: {}",
+                        account,
+                        backoff,
+                        err
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Calls `miner_stop` via JSON-RPC, which makes the node stop mining
     /// blocks.  Useful for integration tests.
     pub async fn miner_stop(&self) -> Result<(), Error> {
-        self.0
-            .request("miner_stop", ())
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))
+        self.request("miner_stop", ()).await
     }
 
     /// Calls `miner_start` via JSON-RPC, which makes the node start mining
     /// blocks.  Useful for integration tests.
     pub async fn miner_start(&self) -> Result<(), Error> {
-        self.0
-            .request("miner_start", [serialize(&1)])
-            .await
-            .map_err(|e| Error::JSONRpcError(e.into()))
+        self.request("miner_start", [serialize(&1)]).await
     }
 }
 
 // Integration tests found in `integration-tests/tests/rpc.rs`.
+
+#[cfg(test)]
+mod rpc_tests {
+    use super::*;
+    use async_trait::async_trait;
+    use ethers_providers::JsonRpcError;
+    use std::{collections::VecDeque, sync::Mutex as StdMutex};
+
+    /// Provider answering every request with an empty proof, after returning
+    /// the queued errors, and recording when each request was received.
+    #[derive(Debug, Default)]
+    struct MockProvider {
+        errors: StdMutex<VecDeque<ProviderError>>,
+        request_times: StdMutex<Vec<Instant>>,
+    }
+
+    impl MockProvider {
+        fn with_errors(codes: &[i64]) -> Self {
+            let errors = codes
+                .iter()
+                .map(|&code| {
+                    ProviderError::JsonRpcClientError(Box::new(HttpClientError::JsonRpcError(
+                        JsonRpcError {
+                            code,
+                            message: "error".to_string(),
+                            data: None,
+                        },
+                    )))
+                })
+                .collect();
+            Self {
+                errors: StdMutex::new(errors),
+                ..Self::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for MockProvider {
+        type Error = ProviderError;
+
+        async fn request<T, R>(&self, _method: &str, _params: T) -> Result<R, ProviderError>
+        where
+            T: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            self.request_times.lock().unwrap().push(Instant::now());
+            if let Some(err) = self.errors.lock().unwrap().pop_front() {
+                return Err(err);
+            }
+            Ok(serde_json::from_value(serde_json::json!({
+                "address": Address::zero(),
+                "balance": Word::zero(),
+                "codeHash": Hash::zero(),
+                "nonce": Word::zero(),
+                "storageHash": Hash::zero(),
+                "accountProof": [],
+                "storageProof": [],
+            }))?)
+        }
+    }
+
+    fn proof_requests() -> Vec<(Address, Vec<Word>)> {
+        vec![
+            (Address::from_low_u64_be(1), vec![]),
+            (Address::from_low_u64_be(2), vec![Word::one()]),
+        ]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_shared_across_calls() {
+        let interval = Duration::from_millis(100);
+        let cli = GethClient::with_min_request_interval(MockProvider::default(), interval);
+        let config = ProofFetchConfig {
+            parallelism: 2,
+            ..ProofFetchConfig::default()
+        };
+        for _ in 0..2 {
+            cli.get_proofs(proof_requests(), BlockNumber::Latest, &config)
+                .await
+                .unwrap();
+        }
+
+        let request_times = cli.provider.request_times.lock().unwrap();
+        assert_eq!(request_times.len(), 4);
+        for times in request_times.windows(2) {
+            assert!(times[1] - times[0] >= interval);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_rate_limited_request() {
+        let cli = GethClient::new(MockProvider::with_errors(&[-32005, 429]));
+        let proofs = cli
+            .get_proofs(
+                proof_requests()[..1].to_vec(),
+                BlockNumber::Latest,
+                &ProofFetchConfig::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(cli.provider.request_times.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_http_too_many_requests() {
+        let body = "Too Many Requests".to_string();
+        let err = serde_json::from_str::<serde_json::Value>(&body).unwrap_err();
+        let cli = GethClient::new(MockProvider::default());
+        cli.provider
+            .errors
+            .lock()
+            .unwrap()
+            .push_back(ProviderError::JsonRpcClientError(Box::new(
+                HttpClientError::SerdeJson { err, text: body },
+            )));
+        cli.get_proofs(
+            proof_requests()[..1].to_vec(),
+            BlockNumber::Latest,
+            &ProofFetchConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cli.provider.request_times.lock().unwrap().len(), 2);
+
+        let err = serde_json::from_str::<serde_json::Value>("Not Found").unwrap_err();
+        assert!(!is_transient(&Error::JSONRpcError(
+            ProviderError::JsonRpcClientError(Box::new(HttpClientError::SerdeJson {
+                err,
+                text: "Not Found".to_string(),
+            }))
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_retry_on_request_error() {
+        // e.g. "missing trie node"
+        let cli = GethClient::new(MockProvider::with_errors(&[-32000]));
+        let result = cli
+            .get_proofs(
+                proof_requests()[..1].to_vec(),
+                BlockNumber::Latest,
+                &ProofFetchConfig::default(),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(cli.provider.request_times.lock().unwrap().len(), 1);
+    }
+}
@@ -8,12 +8,14 @@ pub mod memory;
 pub mod opcode_ids;
 pub mod stack;
 pub mod storage;
+pub mod storage_layout;
 
 pub use {
     memory::{Memory, MemoryAddress},
     opcode_ids::OpcodeId,
     stack::{Stack, StackAddress},
-    storage::Storage,
+    storage::{MappingSlotPreimage, Storage},
+    storage_layout::{ElementSize, SlotPreimage, StorageLocation},
 };
 
 /// Wrapper type over `usize` which represents the program counter of the Evm.
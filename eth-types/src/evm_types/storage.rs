@@ -1,6 +1,7 @@
 //! Doc this
 use crate::Error;
-use crate::{DebugWord, Word};
+use crate::{DebugWord, ToBigEndian, Word};
+use ethers_core::utils::keccak256;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -51,3 +52,61 @@ impl Storage {
         self.get(key).cloned().ok_or(Error::InvalidStorageKey)
     }
 }
+
+/// Preimage of the storage slot of a Solidity mapping entry. The entry for
+/// `key` in a mapping declared at `mapping_slot` lives at
+/// `keccak256(key ++ mapping_slot)`, with both words encoded as 32 big-endian
+/// bytes. Address keys are left-padded with zeros.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MappingSlotPreimage {
+    /// Slot at which the mapping is declared.
+    pub mapping_slot: Word,
+    /// Key of the mapping entry.
+    pub key: Word,
+}
+
+impl MappingSlotPreimage {
+    /// Generate the preimage for `key` in the mapping declared at
+    /// `mapping_slot`.
+    pub fn new(mapping_slot: Word, key: Word) -> Self {
+        Self { mapping_slot, key }
+    }
+
+    /// Returns the 64 bytes hashed to obtain the storage slot.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.key.to_be_bytes());
+        bytes[32..].copy_from_slice(&self.mapping_slot.to_be_bytes());
+        bytes
+    }
+
+    /// Returns the storage slot holding the mapping entry.
+    pub fn slot(&self) -> Word {
+        Word::from_big_endian(&keccak256(self.to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+    use crate::{address, word, ToWord};
+
+    #[test]
+    fn mapping_slot_preimage() {
+        let preimage = MappingSlotPreimage::new(Word::zero(), Word::zero());
+        assert_eq!(preimage.to_bytes(), [0u8; 64]);
+        assert_eq!(
+            preimage.slot(),
+            word!("0xad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5")
+        );
+
+        let key = address!("0x00000000000000000000000000000000deadbeef").to_word();
+        let preimage = MappingSlotPreimage::new(Word::from(3), key);
+        assert_eq!(preimage.to_bytes()[28..32], [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(preimage.to_bytes()[63], 3);
+        assert_eq!(
+            preimage.slot(),
+            word!("0xaad5a3477cd5dd1726c1c6c5fb2fcc4c93f119cd968fe9f3ad9a85e4b4eb1dd2")
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Storage slot calculation for the common Solidity storage layouts, keeping
+//! track of the keccak preimages hashed along the way.
+
+use super::storage::MappingSlotPreimage;
+use crate::{ToBigEndian, Word};
+use ethers_core::utils::keccak256;
+
+/// Keccak preimage hashed while deriving a storage slot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SlotPreimage {
+    /// Entry of a mapping with a value-type key.
+    Mapping(MappingSlotPreimage),
+    /// Entry of a mapping with a `string` or `bytes` key, whose slot is
+    /// `keccak256(key ++ mapping_slot)` with the key left unpadded.
+    BytesKeyMapping {
+        /// Slot at which the mapping is declared.
+        mapping_slot: Word,
+        /// Key of the mapping entry.
+        key: Vec<u8>,
+    },
+    /// Elements of a dynamic array, starting at `keccak256(array_slot)`.
+    DynamicArray(Word),
+}
+
+impl SlotPreimage {
+    /// Returns the bytes hashed to obtain the slot.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Mapping(preimage) => preimage.to_bytes().to_vec(),
+            Self::BytesKeyMapping { mapping_slot, key } => {
+                [&key[..], &mapping_slot.to_be_bytes()[..]].concat()
+            }
+            Self::DynamicArray(array_slot) => array_slot.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the keccak256 hash of the preimage.
+    pub fn hash(&self) -> Word {
+        Word::from_big_endian(&keccak256(self.to_bytes()))
+    }
+}
+
+/// Size of the elements of a Solidity array in storage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ElementSize {
+    /// Value type of the given number of bytes, between 1 and 32. Elements
+    /// of `size` bytes are packed `32 / size` per slot, e.g. two `uint128`.
+    Bytes(u8),
+    /// Type occupying the given number of whole slots, e.g. a struct.
+    Slots(u64),
+}
+
+impl ElementSize {
+    /// Returns the slot offset from the start of the array and the byte
+    /// offset within that slot of the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If a value type size is not between 1 and 32 bytes.
+    fn offsets(self, index: Word) -> (Word, usize) {
+        match self {
+            Self::Bytes(size) => {
+                assert!(
+                    (1..=32).contains(&size),
+                    "value type size must be between 1 and 32 bytes"
+                );
+                let per_slot = Word::from(32 / size);
+                (
+                    index / per_slot,
+                    (index % per_slot).as_usize() * size as usize,
+                )
+            }
+            Self::Slots(slots) => (index.overflowing_mul(Word::from(slots)).0, 0),
+        }
+    }
+}
+
+/// Storage location of a Solidity variable together with the keccak
+/// preimages that were hashed to derive it, outermost first.
+///
+/// Value types smaller than 32 bytes can be packed together in one slot. For
+/// array elements the packing is taken into account and the byte offset
+/// within the slot is tracked; any other location starts at byte offset 0,
+/// as the packing of state variables and struct members depends on their
+/// declaration order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageLocation {
+    slot: Word,
+    offset: usize,
+    preimages: Vec<SlotPreimage>,
+}
+
+impl StorageLocation {
+    /// Location of a state variable declared at `slot`. For a mapping this is
+    /// the slot the entries are derived from, and for a dynamic array the
+    /// slot holding its length.
+    pub fn new(slot: Word) -> Self {
+        Self {
+            slot,
+            offset: 0,
+            preimages: Vec::new(),
+        }
+    }
+
+    /// Returns the slot of the variable.
+    pub fn slot(&self) -> Word {
+        self.slot
+    }
+
+    /// Returns the byte offset of the variable within its slot, counted from
+    /// the least significant byte.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the keccak preimages hashed to derive the slot.
+    pub fn preimages(&self) -> &[SlotPreimage] {
+        &self.preimages
+    }
+
+    /// Location of the entry for `key` in the mapping stored at this
+    /// location. Value-type keys like addresses are left-padded to a word.
+    pub fn mapping_entry(&self, key: Word) -> Self {
+        self.derive(
+            SlotPreimage::Mapping(MappingSlotPreimage::new(self.slot, key)),
+            Word::zero(),
+            0,
+        )
+    }
+
+    /// Location of the entry for a `string` or `bytes` key in the mapping
+    /// stored at this location.
+    pub fn bytes_mapping_entry(&self, key: &[u8]) -> Self {
+        self.derive(
+            SlotPreimage::BytesKeyMapping {
+                mapping_slot: self.slot,
+                key: key.to_vec(),
+            },
+            Word::zero(),
+            0,
+        )
+    }
+
+    /// Location of the element at `index` in the dynamic array stored at
+    /// this location.
+    pub fn array_element(&self, index: Word, element: ElementSize) -> Self {
+        let (slot_offset, offset) = element.offsets(index);
+        self.derive(SlotPreimage::DynamicArray(self.slot), slot_offset, offset)
+    }
+
+    /// Location of the element at `index` in the fixed-size array stored at
+    /// this location.
+    pub fn fixed_array_element(&self, index: Word, element: ElementSize) -> Self {
+        let (slot_offset, offset) = element.offsets(index);
+        Self {
+            slot: self.slot.overflowing_add(slot_offset).0,
+            offset,
+            preimages: self.preimages.clone(),
+        }
+    }
+
+    /// Location `offset` slots after this one, e.g. a struct member.
+    pub fn member(&self, offset: u64) -> Self {
+        Self {
+            slot: self.slot.overflowing_add(Word::from(offset)).0,
+            offset: 0,
+            preimages: self.preimages.clone(),
+        }
+    }
+
+    /// Location `slot_offset` slots and `offset` bytes after the hash of
+    /// `preimage`.
+    fn derive(&self, preimage: SlotPreimage, slot_offset: Word, offset: usize) -> Self {
+        let slot = preimage.hash().overflowing_add(slot_offset).0;
+        let mut preimages = self.preimages.clone();
+        preimages.push(preimage);
+        Self {
+            slot,
+            offset,
+            preimages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod storage_layout_tests {
+    use super::*;
+    use crate::{word, Address, ToWord};
+
+    #[test]
+    fn dynamic_array() {
+        let array = StorageLocation::new(Word::zero());
+        let element = array.array_element(Word::from(2), ElementSize::Slots(1));
+        assert_eq!(
+            element.slot(),
+            word!("0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e565")
+        );
+        assert_eq!(
+            element.preimages(),
+            &[SlotPreimage::DynamicArray(Word::zero())]
+        );
+
+        // Elements spanning several slots
+        let element =
+            StorageLocation::new(Word::from(3)).array_element(Word::from(4), ElementSize::Slots(3));
+        assert_eq!(
+            element.slot(),
+            word!("0xc2575a0e9e593c00f959f8c92f12db2869c3395a3b0502d05e2516446f71f867")
+        );
+    }
+
+    #[test]
+    fn packed_dynamic_array() {
+        // uint128[] at slot 0: two elements per slot
+        let array = StorageLocation::new(Word::zero());
+        let element = array.array_element(Word::from(3), ElementSize::Bytes(16));
+        assert_eq!(
+            element.slot(),
+            word!("0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e564")
+        );
+        assert_eq!(element.offset(), 16);
+
+        // uint8[] at slot 0: 32 elements per slot
+        let element = array.array_element(Word::from(33), ElementSize::Bytes(1));
+        assert_eq!(
+            element.slot(),
+            word!("0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e564")
+        );
+        assert_eq!(element.offset(), 1);
+
+        // Elements that don't divide 32 bytes leave the rest of the slot unused
+        let element = array.array_element(Word::from(4), ElementSize::Bytes(12));
+        assert_eq!(
+            element.slot(),
+            array
+                .array_element(Word::from(2), ElementSize::Slots(1))
+                .slot()
+        );
+        assert_eq!(element.offset(), 0);
+    }
+
+    #[test]
+    fn packed_fixed_array() {
+        // uint64[5] at slot 3: four elements per slot
+        let array = StorageLocation::new(Word::from(3));
+        let element = array.fixed_array_element(Word::from(3), ElementSize::Bytes(8));
+        assert_eq!(element.slot(), Word::from(3));
+        assert_eq!(element.offset(), 24);
+        let element = array.fixed_array_element(Word::from(4), ElementSize::Bytes(8));
+        assert_eq!(element.slot(), Word::from(4));
+        assert_eq!(element.offset(), 0);
+        assert!(element.preimages().is_empty());
+    }
+
+    #[test]
+    fn nested_mapping() {
+        // mapping(address => mapping(address => uint256)) at slot 1
+        let owner = Address::from_low_u64_be(0xdeadbeef).to_word();
+        let spender = Address::from_low_u64_be(0xcafe).to_word();
+        let allowances = StorageLocation::new(Word::one());
+        let inner = allowances.mapping_entry(owner);
+        let entry = inner.mapping_entry(spender);
+        assert_eq!(
+            inner.slot(),
+            word!("0x8003b79ccb3357ef955735bf2d79c8eec95faebc6572403c109c4d6635d6ed76")
+        );
+        assert_eq!(
+            entry.slot(),
+            word!("0x5f5c9828f4afde823ac81510b166c64c81860cae776bc25a070c29cd4204ce31")
+        );
+        assert_eq!(
+            entry.preimages(),
+            &[
+                SlotPreimage::Mapping(MappingSlotPreimage::new(Word::one(), owner)),
+                SlotPreimage::Mapping(MappingSlotPreimage::new(inner.slot(), spender)),
+            ]
+        );
+    }
+
+    #[test]
+    fn struct_member() {
+        // Third slot of a struct stored in mapping(uint256 => Struct) at slot 5
+        let member = StorageLocation::new(Word::from(5))
+            .mapping_entry(Word::from(7))
+            .member(2);
+        assert_eq!(
+            member.slot(),
+            word!("0xeddb6698d7c569ff62ff64f1f1492bf14a54594835ba0faac91f84b4f5d81462")
+        );
+        assert_eq!(member.preimages().len(), 1);
+    }
+
+    #[test]
+    fn bytes_key_mapping() {
+        let entry = StorageLocation::new(Word::from(2)).bytes_mapping_entry(b"abc");
+        assert_eq!(
+            entry.slot(),
+            word!("0x31f76c90c4bd232b01bb0bd40689518175171c0bb64d053d77a6e90319d96718")
+        );
+        assert_eq!(entry.preimages()[0].to_bytes().len(), 3 + 32);
+    }
+}
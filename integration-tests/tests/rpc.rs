@@ -1,5 +1,6 @@
 #![cfg(feature = "rpc")]
 
+use bus_mapping::rpc::ProofFetchConfig;
 use eth_types::{StorageProof, Word};
 use integration_tests::{get_client, CompiledContract, GenDataOutput, CHAIN_ID, CONTRACTS_PATH};
 use lazy_static::lazy_static;
@@ -88,3 +89,31 @@ async fn test_get_proof() {
         .unwrap();
     assert_eq!(expected_storage_proof, proof.storage_proof[0]);
 }
+
+#[tokio::test]
+async fn test_get_proofs() {
+    let (block_num, address) = GEN_DATA.deployments.get("Greeter").unwrap();
+    let requests = vec![
+        (*address, vec![Word::from(0)]),
+        (GEN_DATA.coinbase, vec![]),
+        (GEN_DATA.wallets[0], vec![]),
+    ];
+
+    let cli = get_client();
+    let config = ProofFetchConfig {
+        parallelism: 2,
+        ..ProofFetchConfig::default()
+    };
+    let proofs = cli
+        .get_proofs(requests.clone(), (*block_num).into(), &config)
+        .await
+        .unwrap();
+    assert_eq!(proofs.len(), requests.len());
+    for ((address, keys), proof) in requests.into_iter().zip(proofs) {
+        let expected = cli
+            .get_proof(address, keys, (*block_num).into())
+            .await
+            .unwrap();
+        assert_eq!(expected, proof);
+    }
+}
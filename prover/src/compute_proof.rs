@@ -19,6 +19,7 @@ use zkevm_circuits::evm_circuit::{
 };
 use zkevm_circuits::state_circuit::StateCircuit;
 
+use crate::metrics::{NoopObserver, Phase, PhaseTimer, ProverObserver, TimedCircuit};
 use crate::structs::Proofs;
 
 /// Gathers debug trace(s) from `rpc_url` for block `block_num` with `params`
@@ -28,9 +29,21 @@ pub async fn compute_proof(
     params: &Params<G1Affine>,
     block_num: &u64,
     rpc_url: &str,
+) -> Result<Proofs, Box<dyn std::error::Error>> {
+    compute_proof_with_observer(params, block_num, rpc_url, &NoopObserver).await
+}
+
+/// Same as [`compute_proof`], reporting the duration of every [`Phase`] to
+/// `observer`.
+pub async fn compute_proof_with_observer(
+    params: &Params<G1Affine>,
+    block_num: &u64,
+    rpc_url: &str,
+    observer: &dyn ProverObserver,
 ) -> Result<Proofs, Box<dyn std::error::Error>> {
     // request & build the inputs for the circuits
     let time_started = Instant::now();
+    let mut timer = PhaseTimer::new(observer, *block_num);
     let url = Http::from_str(rpc_url)?;
     let geth_client = GethClient::new(url);
     let builder = BuilderClient::new(geth_client).await?;
@@ -40,9 +53,13 @@ pub async fn compute_proof(
     let evm_proof;
     let state_proof;
     let block = block_convert(&builder.block, &builder.code_db);
+    timer.finish(Phase::Witness);
     {
         // generate evm_circuit proof
-        let circuit = TestCircuit::<Fr>::new(block.clone(), FixedTableTag::iter().collect());
+        let circuit = TimedCircuit::new(TestCircuit::<Fr>::new(
+            block.clone(),
+            FixedTableTag::iter().collect(),
+        ));
 
         // TODO: can this be pre-generated to a file?
         // related
@@ -50,6 +67,9 @@ pub async fn compute_proof(
         // https://github.com/zcash/halo2/issues/449
         let vk = keygen_vk(params, &circuit)?;
         let pk = keygen_pk(params, vk, &circuit)?;
+        // only the synthesis done by the prover is reported
+        circuit.take_synthesis_time();
+        timer.finish(Phase::EvmKeygen);
 
         // Create randomness
         let rng = XorShiftRng::from_seed([
@@ -59,18 +79,27 @@ pub async fn compute_proof(
 
         // create a proof
         let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-        create_proof(params, &pk, &[circuit], &[], rng, &mut transcript)?;
+        let circuits = [circuit];
+        create_proof(params, &pk, &circuits, &[], rng, &mut transcript)?;
         evm_proof = transcript.finalize();
+        timer.finish_split(
+            Phase::EvmSynthesis,
+            circuits[0].take_synthesis_time(),
+            Phase::EvmCommitment,
+        );
     }
 
     {
         // generate state_circuit proof
         const N_ROWS: usize = 1 << 16;
-        let circuit = StateCircuit::<Fr, N_ROWS>::new(block.randomness, block.rws);
+        let circuit =
+            TimedCircuit::new(StateCircuit::<Fr, N_ROWS>::new(block.randomness, block.rws));
 
         // TODO: same quest like in the first scope
         let vk = keygen_vk(params, &circuit)?;
         let pk = keygen_pk(params, vk, &circuit)?;
+        circuit.take_synthesis_time();
+        timer.finish(Phase::StateKeygen);
 
         // Create randomness
         let rng = XorShiftRng::from_seed([
@@ -80,8 +109,14 @@ pub async fn compute_proof(
 
         // create a proof
         let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-        create_proof(params, &pk, &[circuit], &[], rng, &mut transcript)?;
+        let circuits = [circuit];
+        create_proof(params, &pk, &circuits, &[], rng, &mut transcript)?;
         state_proof = transcript.finalize();
+        timer.finish_split(
+            Phase::StateSynthesis,
+            circuits[0].take_synthesis_time(),
+            Phase::StateCommitment,
+        );
     }
 
     let ret = Proofs {
@@ -1,3 +1,4 @@
 pub mod compute_proof;
+pub mod metrics;
 pub mod shared_state;
 pub mod structs;
@@ -0,0 +1,189 @@
+//! Hooks to observe the phases of a proof computation, e.g. to export their
+//! timings as metrics.
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Layouter,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Phases of [`crate::compute_proof::compute_proof_with_observer`], reported
+/// in this order.
+///
+/// The creation of each proof is split into the synthesis of the circuit and
+/// the remaining prover work, measured as the `create_proof` time minus the
+/// synthesis time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Fetching the block, its traces and the touched state from the rpc node
+    /// and building the circuit inputs from them.
+    Witness,
+    /// Generating the evm circuit verifying and proving keys.
+    EvmKeygen,
+    /// Synthesizing the evm circuit while creating its proof: loading the
+    /// lookup tables and assigning the witness.
+    EvmSynthesis,
+    /// Committing to the evm circuit polynomials and computing the opening
+    /// proofs.
+    EvmCommitment,
+    /// Generating the state circuit verifying and proving keys.
+    StateKeygen,
+    /// Synthesizing the state circuit while creating its proof.
+    StateSynthesis,
+    /// Committing to the state circuit polynomials and computing the opening
+    /// proofs.
+    StateCommitment,
+}
+
+/// Receives the duration of every completed phase of a proof computation.
+pub trait ProverObserver: Send + Sync {
+    /// Called once `phase` of the proof for `block_num` completed.
+    fn phase_completed(&self, block_num: u64, phase: Phase, duration: Duration);
+}
+
+/// Observer ignoring all events.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl ProverObserver for NoopObserver {
+    fn phase_completed(&self, _block_num: u64, _phase: Phase, _duration: Duration) {}
+}
+
+/// Measures consecutive phases and reports them to an observer.
+pub(crate) struct PhaseTimer<'a> {
+    observer: &'a dyn ProverObserver,
+    block_num: u64,
+    started: Instant,
+}
+
+impl<'a> PhaseTimer<'a> {
+    pub(crate) fn new(observer: &'a dyn ProverObserver, block_num: u64) -> Self {
+        Self {
+            observer,
+            block_num,
+            started: Instant::now(),
+        }
+    }
+
+    /// Reports `phase` as completed and starts measuring the next one.
+    pub(crate) fn finish(&mut self, phase: Phase) {
+        let now = Instant::now();
+        self.observer
+            .phase_completed(self.block_num, phase, now.duration_since(self.started));
+        self.started = now;
+    }
+
+    /// Reports the time since the last phase as `first`, which took
+    /// `first_duration` of it, followed by `second`, which took the rest.
+    pub(crate) fn finish_split(&mut self, first: Phase, first_duration: Duration, second: Phase) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.started);
+        let first_duration = first_duration.min(elapsed);
+        self.observer
+            .phase_completed(self.block_num, first, first_duration);
+        self.observer
+            .phase_completed(self.block_num, second, elapsed - first_duration);
+        self.started = now;
+    }
+}
+
+/// Circuit wrapper accumulating the time spent in the `synthesize` of the
+/// wrapped circuit, shared with the circuits returned by `without_witnesses`.
+pub(crate) struct TimedCircuit<C> {
+    circuit: C,
+    synthesis: Arc<Mutex<Duration>>,
+}
+
+impl<C> TimedCircuit<C> {
+    pub(crate) fn new(circuit: C) -> Self {
+        Self {
+            circuit,
+            synthesis: Arc::default(),
+        }
+    }
+
+    /// Returns the time spent synthesizing since the last call.
+    pub(crate) fn take_synthesis_time(&self) -> Duration {
+        std::mem::take(&mut *self.synthesis.lock().unwrap())
+    }
+}
+
+impl<F: FieldExt, C: Circuit<F>> Circuit<F> for TimedCircuit<C> {
+    type Config = C::Config;
+    type FloorPlanner = C::FloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            circuit: self.circuit.without_witnesses(),
+            synthesis: self.synthesis.clone(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        let started = Instant::now();
+        let result = self.circuit.synthesize(config, layouter);
+        *self.synthesis.lock().unwrap() += started.elapsed();
+        result
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingObserver(Mutex<Vec<(u64, Phase, Duration)>>);
+
+    impl ProverObserver for RecordingObserver {
+        fn phase_completed(&self, block_num: u64, phase: Phase, duration: Duration) {
+            self.0.lock().unwrap().push((block_num, phase, duration));
+        }
+    }
+
+    #[test]
+    fn phase_timer_reports_consecutive_phases() {
+        let pause = Duration::from_millis(10);
+        let observer = RecordingObserver::default();
+        let started = Instant::now();
+        let mut timer = PhaseTimer::new(&observer, 7);
+        std::thread::sleep(pause);
+        timer.finish(Phase::Witness);
+        std::thread::sleep(pause);
+        timer.finish(Phase::EvmKeygen);
+        std::thread::sleep(2 * pause);
+        timer.finish_split(Phase::EvmSynthesis, pause, Phase::EvmCommitment);
+        let elapsed = started.elapsed();
+
+        let reports = observer.0.into_inner().unwrap();
+        assert_eq!(
+            reports
+                .iter()
+                .map(|(block_num, phase, _)| (*block_num, *phase))
+                .collect::<Vec<_>>(),
+            vec![
+                (7, Phase::Witness),
+                (7, Phase::EvmKeygen),
+                (7, Phase::EvmSynthesis),
+                (7, Phase::EvmCommitment),
+            ]
+        );
+        for (_, phase, duration) in reports.iter() {
+            assert!(*duration >= pause, "{:?}", phase);
+        }
+        assert_eq!(reports[2].2, pause);
+        // The phases don't overlap, so they sum up to at most the total time
+        assert!(
+            reports
+                .iter()
+                .map(|(_, _, duration)| *duration)
+                .sum::<Duration>()
+                <= elapsed
+        );
+    }
+}
@@ -7,7 +7,8 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
-use crate::compute_proof::compute_proof;
+use crate::compute_proof::compute_proof_with_observer;
+use crate::metrics::{NoopObserver, ProverObserver};
 use crate::structs::{ProofRequestOptions, Proofs};
 
 #[derive(Debug, Clone)]
@@ -25,16 +26,24 @@ pub struct RwState {
 #[derive(Clone)]
 pub struct SharedState {
     pub rw: Arc<Mutex<RwState>>,
+    pub observer: Arc<dyn ProverObserver>,
 }
 
 impl SharedState {
     pub fn new() -> SharedState {
+        Self::with_observer(Arc::new(NoopObserver))
+    }
+
+    /// Creates the state with `observer` receiving the phase timings of
+    /// every computed proof.
+    pub fn with_observer(observer: Arc<dyn ProverObserver>) -> SharedState {
         Self {
             rw: Arc::new(Mutex::new(RwState {
                 tasks: Vec::new(),
                 pending_tasks: 0,
                 params_cache: HashMap::new(),
             })),
+            observer,
         }
     }
 
@@ -120,10 +129,11 @@ impl SharedState {
             tokio::spawn(async move {
                 // lazily load the file and cache it
                 let param = self_copy.load_param(&pending_task_copy.options.param).await;
-                let res = compute_proof(
+                let res = compute_proof_with_observer(
                     param.as_ref(),
                     &pending_task_copy.options.block,
                     &pending_task_copy.options.rpc,
+                    self_copy.observer.as_ref(),
                 )
                 .await;
 
@@ -6,7 +6,7 @@ use crate::{
             RandomLinearCombination,
         },
     },
-    util::Expr,
+    util::{keccak::compute_keccak, Expr},
 };
 use bus_mapping::evm::OpcodeId;
 use eth_types::Field;
@@ -19,8 +19,7 @@ use halo2_proofs::{
     plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, VirtualCells},
     poly::Rotation,
 };
-use keccak256::plain::Keccak;
-use std::{convert::TryInto, vec};
+use std::vec;
 
 use super::param::{KECCAK_WIDTH, PUSH_TABLE_WIDTH};
 
@@ -662,22 +661,7 @@ fn get_push_size(byte: u8) -> u64 {
 }
 
 fn keccak<F: Field>(msg: &[u8], r: F) -> F {
-    let mut keccak = Keccak::default();
-    keccak.update(msg);
-    RandomLinearCombination::<F, 32>::random_linear_combine(keccak.digest().try_into().unwrap(), r)
-}
-
-fn into_words(message: &[u8]) -> Vec<u64> {
-    let words_total = message.len() / 8;
-    let mut words: Vec<u64> = vec![0; words_total];
-
-    for i in 0..words_total {
-        let mut word_bits: [u8; 8] = Default::default();
-        word_bits.copy_from_slice(&message[i * 8..i * 8 + 8]);
-        words[i] = u64::from_le_bytes(word_bits);
-    }
-
-    words
+    RandomLinearCombination::<F, 32>::random_linear_combine(compute_keccak(msg), r)
 }
 
 fn linear_combine<F: Field>(bytes: Vec<u8>, r: F) -> F {
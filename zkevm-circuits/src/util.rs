@@ -1,6 +1,8 @@
 //! Common utility traits and functions.
 use eth_types::Field;
 
+pub mod keccak;
+
 pub use gadgets::util::Expr;
 
 pub(crate) fn random_linear_combine_word<F: Field>(bytes: [u8; 32], randomness: F) -> F {
@@ -0,0 +1,274 @@
+//! Keccak witness utilities: padding, word packing and digest computation
+//! for inputs spanning any number of rate blocks.
+
+use eth_types::Field;
+use gadgets::util::expr_from_bytes;
+use halo2_proofs::{arithmetic::FieldExt, plonk::Expression};
+use keccak256::plain::Keccak;
+use std::convert::TryInto;
+
+/// Number of bytes absorbed per keccak-f permutation.
+pub const RATE: usize = 136;
+/// Number of bytes packed into a single word.
+pub const WORD_BYTES: usize = 8;
+/// Number of words absorbed per keccak-f permutation.
+pub const RATE_IN_WORDS: usize = RATE / WORD_BYTES;
+
+/// Applies the keccak `pad10*1` padding, extending the message to the next
+/// multiple of `RATE` bytes. A message that already fills a complete block
+/// gets a full block of padding.
+pub fn pad(message: &[u8]) -> Vec<u8> {
+    let padding_total = RATE - (message.len() % RATE);
+    let mut padded = message.to_vec();
+    if padding_total == 1 {
+        padded.push(0x81);
+    } else {
+        padded.push(0x01);
+        padded.resize(message.len() + padding_total - 1, 0x00);
+        padded.push(0x80);
+    }
+    padded
+}
+
+/// Returns the keccak256 digest of the message.
+pub fn compute_keccak(message: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::default();
+    keccak.update(message);
+    keccak.digest().try_into().unwrap()
+}
+
+/// Packs `bytes` as a little-endian integer into a field element.
+/// Returns `None` when the integer has more significant bits than the field's
+/// capacity, instead of silently reducing it modulo the field order. Integers
+/// that are wider than the capacity but still below the modulus are rejected
+/// as well.
+pub fn pack_le_bytes<F: FieldExt>(bytes: &[u8]) -> Option<F> {
+    let bits = bytes
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| (i + 1) * 8 - bytes[i].leading_zeros() as usize);
+    if bits > F::CAPACITY as usize {
+        return None;
+    }
+    Some(bytes.iter().rev().fold(F::zero(), |acc, &byte| {
+        acc * F::from(256) + F::from(byte as u64)
+    }))
+}
+
+/// Byte order used to pack message bytes into words, so the words can match
+/// the lane convention of the keccak circuit the table is checked against.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WordEndianness {
+    /// The first byte of a word is its least significant byte, as in the
+    /// keccak lanes.
+    Little,
+    /// The first byte of a word is its most significant byte.
+    Big,
+}
+
+/// Packs the message into words of `WORD_BYTES` bytes each, using the given
+/// byte order. Trailing bytes that don't fill a complete word are ignored.
+pub fn into_words<F: Field>(message: &[u8], endianness: WordEndianness) -> Vec<F> {
+    message
+        .chunks_exact(WORD_BYTES)
+        .map(|word| {
+            let word = match endianness {
+                WordEndianness::Little => word.to_vec(),
+                WordEndianness::Big => word.iter().rev().cloned().collect(),
+            };
+            pack_le_bytes(&word).expect("word does not fit in the field")
+        })
+        .collect()
+}
+
+/// Expression counterpart of `into_words`, packing byte expressions into
+/// word expressions using the given byte order.
+pub fn into_words_expr<F: Field>(
+    message: &[Expression<F>],
+    endianness: WordEndianness,
+) -> Vec<Expression<F>> {
+    assert!(
+        WORD_BYTES * 8 <= F::CAPACITY as usize,
+        "word does not fit in the field"
+    );
+    message
+        .chunks_exact(WORD_BYTES)
+        .map(|word| {
+            let word: Vec<Expression<F>> = match endianness {
+                WordEndianness::Little => word.to_vec(),
+                WordEndianness::Big => word.iter().rev().cloned().collect(),
+            };
+            expr_from_bytes(&word)
+        })
+        .collect()
+}
+
+/// Pads the message and returns the words absorbed by each keccak-f
+/// permutation, `RATE_IN_WORDS` words per block.
+pub fn into_blocks<F: Field>(message: &[u8], endianness: WordEndianness) -> Vec<Vec<F>> {
+    pad(message)
+        .chunks(RATE)
+        .map(|block| into_words(block, endianness))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff::PrimeField;
+    use halo2_proofs::pairing::bn256::Fr;
+    use keccak256::{common::State, keccak_arith, plain::KeccakF};
+    use sha3::{Digest, Keccak256};
+
+    /// Message lengths around the rate boundary
+    const EDGE_LENGTHS: [usize; 3] = [RATE - 1, RATE, RATE + 1];
+
+    /// Absorbs the padded blocks with the plain keccak-f permutation and
+    /// squeezes the digest, bypassing the padding of the keccak256 crate.
+    fn absorb_blocks(message: &[u8]) -> [u8; 32] {
+        let keccak_f = KeccakF::default();
+        let mut state: State = [[0; 5]; 5];
+        for block in into_blocks::<Fr>(message, WordEndianness::Little) {
+            for (i, word) in block.iter().enumerate() {
+                let repr = word.to_repr();
+                state[i % 5][i / 5] ^= u64::from_le_bytes(repr[..8].try_into().unwrap());
+            }
+            keccak_f.permutations(&mut state);
+        }
+        let mut digest = [0u8; 32];
+        for (i, chunk) in digest.chunks_exact_mut(WORD_BYTES).enumerate() {
+            chunk.copy_from_slice(&state[i][0].to_le_bytes());
+        }
+        digest
+    }
+
+    #[test]
+    fn pad_lengths() {
+        for (len, padded_len) in [(0, RATE), (1, RATE), (RATE - 1, RATE), (RATE, 2 * RATE)] {
+            let padded = pad(&vec![0xaa; len]);
+            assert_eq!(padded.len(), padded_len);
+            assert_eq!(&padded[..len], &vec![0xaa; len][..]);
+        }
+        // A single padding byte carries both the start and end markers
+        assert_eq!(pad(&[0xaa; RATE - 1])[RATE - 1], 0x81);
+        let padded = pad(&[0xaa; RATE]);
+        assert_eq!(padded[RATE], 0x01);
+        assert_eq!(padded[2 * RATE - 1], 0x80);
+    }
+
+    #[test]
+    fn multi_block_words() {
+        for len in [0, RATE - 1, RATE, 3 * RATE + 5] {
+            let message: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let blocks = into_blocks::<Fr>(&message, WordEndianness::Little);
+            assert_eq!(blocks.len(), len / RATE + 1);
+            for block in blocks.iter() {
+                assert_eq!(block.len(), RATE_IN_WORDS);
+            }
+            assert_eq!(
+                blocks.concat(),
+                into_words::<Fr>(&pad(&message), WordEndianness::Little)
+            );
+        }
+    }
+
+    #[test]
+    fn words_packing() {
+        let message: Vec<u8> = (0u8..20).collect();
+        let words = into_words::<Fr>(&message, WordEndianness::Little);
+        assert_eq!(words.len(), 2);
+        for (word, chunk) in words.iter().zip(message.chunks_exact(WORD_BYTES)) {
+            assert_eq!(
+                *word,
+                Fr::from(u64::from_le_bytes(chunk.try_into().unwrap()))
+            );
+        }
+        let words = into_words::<Fr>(&message, WordEndianness::Big);
+        for (word, chunk) in words.iter().zip(message.chunks_exact(WORD_BYTES)) {
+            assert_eq!(
+                *word,
+                Fr::from(u64::from_be_bytes(chunk.try_into().unwrap()))
+            );
+        }
+        // The full u64 range packs without reduction
+        assert_eq!(
+            into_words::<Fr>(&[0xff; 8], WordEndianness::Little),
+            vec![Fr::from(u64::MAX)]
+        );
+        // Integers wider than the field capacity are rejected
+        assert_eq!(pack_le_bytes::<Fr>(&[0xff; 32]), None);
+        let mut bytes = [0xff; 32];
+        bytes[31] = 0x3f;
+        assert_eq!(pack_le_bytes::<Fr>(&bytes), None);
+        // Only the significant bits count towards the capacity
+        bytes[31] = 0x1f;
+        assert_eq!(
+            pack_le_bytes::<Fr>(&bytes),
+            Some(Fr::from_repr(bytes).unwrap())
+        );
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        assert_eq!(pack_le_bytes::<Fr>(&bytes), Some(Fr::from(1)));
+        assert_eq!(pack_le_bytes::<Fr>(&[]), Some(Fr::from(0)));
+    }
+
+    #[test]
+    fn words_expr_packing() {
+        let message: Vec<u8> = (0u8..20).map(|i| i.wrapping_mul(37)).collect();
+        let bytes: Vec<Expression<Fr>> = message
+            .iter()
+            .map(|&byte| Expression::Constant(Fr::from(byte as u64)))
+            .collect();
+        for endianness in [WordEndianness::Little, WordEndianness::Big] {
+            let words: Vec<Fr> = into_words_expr(&bytes, endianness)
+                .iter()
+                .map(|word| {
+                    word.evaluate(
+                        &|scalar| scalar,
+                        &|_| unreachable!("selector column"),
+                        &|_, _, _| unreachable!("fixed column"),
+                        &|_, _, _| unreachable!("advice column"),
+                        &|_, _, _| unreachable!("instance column"),
+                        &|a| -a,
+                        &|a, b| a + b,
+                        &|a, b| a * b,
+                        &|a, scalar| a * scalar,
+                    )
+                })
+                .collect();
+            assert_eq!(
+                words,
+                into_words::<Fr>(&message, endianness),
+                "{:?}",
+                endianness
+            );
+        }
+    }
+
+    #[test]
+    fn keccak_edge_lengths() {
+        for len in EDGE_LENGTHS {
+            let message: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+            let mut keccak = keccak_arith::Keccak::default();
+            keccak.update(&message);
+            let digest = compute_keccak(&message);
+            assert_eq!(digest[..], keccak.digest()[..], "length {}", len);
+            assert_eq!(digest, absorb_blocks(&message), "length {}", len);
+            assert_eq!(pad(&message).len(), (len / RATE + 1) * RATE);
+        }
+        assert_eq!(compute_keccak(&[]), *keccak256::EMPTY_HASH);
+    }
+
+    #[test]
+    fn keccak_multi_block() {
+        for len in [0, 1, RATE - 1, RATE, RATE + 1, 2 * RATE, 5 * RATE + 3] {
+            let message: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            assert_eq!(
+                compute_keccak(&message)[..],
+                Keccak256::digest(&message)[..],
+                "length {}",
+                len
+            );
+        }
+    }
+}